@@ -1,62 +1,499 @@
-use hashbrown::HashSet;
 use lazy_static::lazy_static;
+use lru::LruCache;
+use memchr::memmem;
+use smallvec::SmallVec;
+use std::num::NonZeroUsize;
+use std::ops::Range;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
 pub use regex::Error;
 
 /// Wrapper around [`regex::bytes::Regex`]
+///
+/// Plain-literal patterns bypass the regex engine in favor of a
+/// `memchr`-based substring search; everything else falls back to a
+/// compiled [`regex::bytes::Regex`].
 #[derive(Clone)]
-pub struct Regex(Arc<regex::bytes::Regex>);
+pub struct Regex(Arc<Inner>);
+
+struct Inner {
+    pattern: String,
+    repr: Repr,
+}
+
+enum Repr {
+    /// A plain literal substring, matched with `memchr::memmem`.
+    Literal(Vec<u8>),
+    /// A fully compiled regex, used for anything that isn't a plain literal.
+    Compiled(regex::bytes::Regex),
+}
+
+/// Regex metacharacters that disqualify a pattern from the literal fast path.
+const REGEX_METACHARS: &[char] = &[
+    '\\', '.', '+', '*', '?', '(', ')', '|', '[', ']', '{', '}', '^', '$',
+];
+
+/// Returns `true` if `pattern` contains no regex metacharacters, i.e. it
+/// would match only its own exact bytes.
+fn is_literal(pattern: &str) -> bool {
+    !pattern.chars().any(|c| REGEX_METACHARS.contains(&c))
+}
+
+/// Default capacity of the global [`REGEX_CACHE`], i.e. the number of
+/// distinct compiled patterns kept alive before the least-recently-used
+/// entry is evicted.
+const DEFAULT_REGEX_CACHE_CAPACITY: usize = 4096;
 
 lazy_static! {
-    static ref REGEX_POOL: Mutex<HashSet<Regex>> = Mutex::new(HashSet::new());
+    // LRU cache of compiled patterns, keyed on the pattern string plus options.
+    static ref REGEX_CACHE: Mutex<LruCache<(String, Options), Arc<Inner>>> = Mutex::new(
+        LruCache::new(NonZeroUsize::new(DEFAULT_REGEX_CACHE_CAPACITY).unwrap())
+    );
 }
 
-impl Drop for Regex {
-    fn drop(&mut self) {
-        // The logic here is a bit hacky, we check the strong_count for 2,
-        // because we have this reference and the one that lives in the HashSet.
-        // Once we will call remove on the pool, we will enter this function again
-        // this time with the entry that came from the HashSet, it too has a strong_count
-        // of 2, because we have not finished dropping the previous Arc.
-        // In order to distinguish between the two, we are doing a small hack here and
-        // take a weak reference prior to calling remove, this way the 2nd drop can know
-        // it's the one that came from the pool and does not need to do any additional
-        // work.
-        // This is how we solve the deadlock of the mutex being acquired more than once.
-        if Arc::strong_count(&self.0) == 2 && Arc::weak_count(&self.0) == 0 {
-            let _dummy_weak = Arc::downgrade(&self.0);
-            let mut pool = REGEX_POOL.lock().unwrap();
-            pool.remove(&self);
-            return;
+/// Compilation options for [`Regex`], mirroring a subset of
+/// [`regex::bytes::RegexBuilder`]'s knobs. Part of the cache key: two
+/// regexes compiled from the same pattern string but with different options
+/// are distinct cache entries.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Options {
+    case_insensitive: bool,
+    multi_line: bool,
+    dot_matches_new_line: bool,
+    unicode: bool,
+    size_limit: usize,
+    dfa_size_limit: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            case_insensitive: false,
+            multi_line: false,
+            dot_matches_new_line: false,
+            unicode: false,
+            size_limit: 10 * (1 << 20),
+            dfa_size_limit: 2 * (1 << 20),
         }
     }
 }
 
+/// Builder for [`Regex`], forwarding the subset of
+/// [`regex::bytes::RegexBuilder`] options that filter callers need.
+pub struct RegexBuilder<'a> {
+    pattern: &'a str,
+    options: Options,
+}
+
+impl<'a> RegexBuilder<'a> {
+    fn new(pattern: &'a str) -> Self {
+        Self {
+            pattern,
+            options: Options::default(),
+        }
+    }
+
+    /// Enables case-insensitive matching.
+    pub fn case_insensitive(mut self, yes: bool) -> Self {
+        self.options.case_insensitive = yes;
+        self
+    }
+
+    /// Enables multi-line mode, where `^` and `$` match the start/end of a line.
+    pub fn multi_line(mut self, yes: bool) -> Self {
+        self.options.multi_line = yes;
+        self
+    }
+
+    /// Enables `.` matching `\n` in addition to every other byte.
+    pub fn dot_matches_new_line(mut self, yes: bool) -> Self {
+        self.options.dot_matches_new_line = yes;
+        self
+    }
+
+    /// Toggles Unicode mode (disabled by default, matching this wrapper's prior behaviour).
+    pub fn unicode(mut self, yes: bool) -> Self {
+        self.options.unicode = yes;
+        self
+    }
+
+    /// Sets an approximate limit, in bytes, on the size of the compiled program.
+    pub fn size_limit(mut self, bytes: usize) -> Self {
+        self.options.size_limit = bytes;
+        self
+    }
+
+    /// Sets an approximate limit, in bytes, on how big the lazy DFA can get.
+    pub fn dfa_size_limit(mut self, bytes: usize) -> Self {
+        self.options.dfa_size_limit = bytes;
+        self
+    }
+
+    /// Compiles the regex, returning a cached handle if an identical
+    /// pattern/options pair is already in the cache.
+    pub fn build(self) -> Result<Regex, Error> {
+        Regex::compile(self.pattern, self.options)
+    }
+}
+
+impl Regex {
+    /// Returns a [`RegexBuilder`] for compiling `pattern` with non-default options.
+    pub fn builder(pattern: &str) -> RegexBuilder<'_> {
+        RegexBuilder::new(pattern)
+    }
+
+    /// Overrides the capacity of the global regex cache.
+    ///
+    /// Entries beyond the new capacity are evicted immediately,
+    /// least-recently-used first.
+    pub fn set_cache_capacity(capacity: NonZeroUsize) {
+        REGEX_CACHE.lock().unwrap().resize(capacity);
+    }
+
+    fn compile(pattern: &str, options: Options) -> Result<Self, Error> {
+        let key = (pattern.to_owned(), options.clone());
+
+        if let Some(inner) = REGEX_CACHE.lock().unwrap().get(&key) {
+            return Ok(Self(Arc::clone(inner)));
+        }
+
+        // Compile outside the lock: a large-but-under-limit pattern can take
+        // a while, and holding the mutex for that long would block every
+        // other thread's cache lookups, cheap hits included.
+        //
+        // A plain literal with default-ish flags needs no automaton at all;
+        // case-insensitive matching still goes through the regex engine
+        // since byte-for-byte search can't fold case on its own.
+        let repr = if !options.case_insensitive && is_literal(pattern) {
+            Repr::Literal(pattern.as_bytes().to_vec())
+        } else {
+            Repr::Compiled(
+                ::regex::bytes::RegexBuilder::new(pattern)
+                    .case_insensitive(options.case_insensitive)
+                    .multi_line(options.multi_line)
+                    .dot_matches_new_line(options.dot_matches_new_line)
+                    .unicode(options.unicode)
+                    .size_limit(options.size_limit)
+                    .dfa_size_limit(options.dfa_size_limit)
+                    .build()?,
+            )
+        };
+
+        let inner = Arc::new(Inner {
+            pattern: pattern.to_owned(),
+            repr,
+        });
+
+        let mut cache = REGEX_CACHE.lock().unwrap();
+        // Another thread may have compiled and inserted the same key while
+        // we were compiling ours; prefer theirs so concurrent callers for
+        // the same pattern converge on a single shared `Arc`, discarding our
+        // own redundant compile.
+        let inner = match cache.get(&key) {
+            Some(existing) => Arc::clone(existing),
+            None => {
+                cache.put(key, Arc::clone(&inner));
+                inner
+            }
+        };
+
+        Ok(Self(inner))
+    }
+}
+
 impl FromStr for Regex {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Error> {
-        let regex = Self(Arc::new(
-            ::regex::bytes::RegexBuilder::new(s)
-                .unicode(false)
-                .build()?,
-        ));
-
-        let mut pool = REGEX_POOL.lock().unwrap();
-        Ok(pool.get_or_insert(regex).clone())
+        Self::compile(s, Options::default())
     }
 }
 
 impl Regex {
     /// Returns true if and only if the regex matches the string given.
     pub fn is_match(&self, text: &[u8]) -> bool {
-        self.0.is_match(text)
+        match &self.0.repr {
+            Repr::Literal(literal) => memmem::find(text, literal).is_some(),
+            Repr::Compiled(regex) => regex.is_match(text),
+        }
     }
 
     /// Returns the original string of this regex.
     pub fn as_str(&self) -> &str {
-        self.0.as_str()
+        &self.0.pattern
+    }
+
+    /// Returns the capture groups for the leftmost match in `text`, if any.
+    ///
+    /// This is strictly opt-in: computing captures walks a slower path than
+    /// [`Regex::is_match`], so only call it for expressions that actually
+    /// bind a capture group rather than on the hot yes/no matching path.
+    /// `captures(text).is_some()` always agrees with `is_match(text)`,
+    /// including on the literal fast path: a plain literal has no groups
+    /// beyond group 0 (the whole match), which is populated either way.
+    pub fn captures<'t>(&self, text: &'t [u8]) -> Option<Captures<'t>> {
+        match &self.0.repr {
+            Repr::Literal(literal) => {
+                let start = memmem::find(text, literal)?;
+                let range = start..start + literal.len();
+                Some(Captures(CapturesRepr::Literal {
+                    bytes: &text[range.clone()],
+                    range,
+                }))
+            }
+            Repr::Compiled(regex) => regex.captures(text).map(|c| Captures(CapturesRepr::Compiled(c))),
+        }
+    }
+}
+
+/// A set of capture groups found in text, as produced by [`Regex::captures`].
+pub struct Captures<'t>(CapturesRepr<'t>);
+
+enum CapturesRepr<'t> {
+    Compiled(regex::bytes::Captures<'t>),
+    /// A literal match has only group 0, the whole match itself.
+    Literal { bytes: &'t [u8], range: Range<usize> },
+}
+
+impl<'t> Captures<'t> {
+    /// Returns the match for the capture group at index `i`, if it participated in the match.
+    pub fn get(&self, i: usize) -> Option<Match<'t>> {
+        match &self.0 {
+            CapturesRepr::Compiled(captures) => captures.get(i).map(Match::compiled),
+            CapturesRepr::Literal { bytes, range } if i == 0 => Some(Match::literal(bytes, range.clone())),
+            CapturesRepr::Literal { .. } => None,
+        }
+    }
+
+    /// Returns the match for the named capture group `name`, if it participated in the match.
+    pub fn name(&self, name: &str) -> Option<Match<'t>> {
+        match &self.0 {
+            CapturesRepr::Compiled(captures) => captures.name(name).map(Match::compiled),
+            // A plain literal has no named groups to extract.
+            CapturesRepr::Literal { .. } => None,
+        }
+    }
+}
+
+/// A single capture group match: its matched byte slice and offset range.
+pub struct Match<'t>(MatchRepr<'t>);
+
+enum MatchRepr<'t> {
+    Compiled(regex::bytes::Match<'t>),
+    Literal { bytes: &'t [u8], range: Range<usize> },
+}
+
+impl<'t> Match<'t> {
+    fn compiled(m: regex::bytes::Match<'t>) -> Self {
+        Self(MatchRepr::Compiled(m))
+    }
+
+    fn literal(bytes: &'t [u8], range: Range<usize>) -> Self {
+        Self(MatchRepr::Literal { bytes, range })
+    }
+
+    /// Returns the byte slice that matched.
+    pub fn as_bytes(&self) -> &'t [u8] {
+        match &self.0 {
+            MatchRepr::Compiled(m) => m.as_bytes(),
+            MatchRepr::Literal { bytes, .. } => bytes,
+        }
+    }
+
+    /// Returns the byte offset range of the match within the original text.
+    pub fn range(&self) -> Range<usize> {
+        match &self.0 {
+            MatchRepr::Compiled(m) => m.range(),
+            MatchRepr::Literal { range, .. } => range.clone(),
+        }
+    }
+}
+
+/// Wrapper around [`regex::bytes::RegexSet`], matching N patterns in one pass.
+#[derive(Clone)]
+pub struct RegexSet(Arc<regex::bytes::RegexSet>);
+
+impl RegexSet {
+    /// Compiles a `RegexSet` from an iterator of patterns.
+    pub fn new<I, S>(patterns: I) -> Result<Self, Error>
+    where
+        S: AsRef<str>,
+        I: IntoIterator<Item = S>,
+    {
+        Ok(Self(Arc::new(regex::bytes::RegexSet::new(patterns)?)))
+    }
+
+    /// Fuses several already-compiled [`Regex`] predicates into a single
+    /// `RegexSet`, preserving their relative order (`matches` indices line
+    /// up with the input order). This is the call the filter compiler is
+    /// meant to make once it detects two or more regex predicates over the
+    /// same field, so it can test all of them in one pass instead of
+    /// running each `Regex::is_match` independently.
+    ///
+    /// BLOCKED: this source tree contains no filter-compiler/expression
+    /// module to call this from — there is nothing here that parses
+    /// predicates, groups them by field, or walks a compiled filter. The
+    /// detection-and-rewrite half of this request cannot be wired up until
+    /// that module exists; only this mechanical fusion step is implemented.
+    pub fn fuse<'a, I>(regexes: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = &'a Regex>,
+    {
+        Self::new(regexes.into_iter().map(Regex::as_str))
+    }
+
+    /// Returns true if and only if at least one pattern in the set matches the text given.
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        self.0.is_match(text)
+    }
+
+    /// Returns the indices, in pattern order, of every pattern that matches the text given.
+    pub fn matches(&self, text: &[u8]) -> SmallVec<[usize; 4]> {
+        self.0.matches(text).into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `REGEX_CACHE` is a process-wide singleton; `cargo test` runs test
+    // functions concurrently by default, so any test that changes the
+    // global cache capacity must hold this lock for the duration, so that
+    // no two such tests resize the cache under each other. Tests that do
+    // not take the lock can still see their entries evicted meanwhile.
+    static CACHE_CAPACITY_TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn regex_set_is_match() {
+        let set = RegexSet::new(["^foo", "bar$"]).unwrap();
+        assert!(set.is_match(b"foobaz"));
+        assert!(set.is_match(b"bazbar"));
+        assert!(!set.is_match(b"baz"));
+    }
+
+    #[test]
+    fn regex_set_fuse_matches_agree_with_individual_regexes() {
+        let a = Regex::from_str("^foo").unwrap();
+        let b = Regex::from_str("bar$").unwrap();
+        let c = Regex::from_str("baz").unwrap();
+        let regexes = [a.clone(), b.clone(), c.clone()];
+
+        let fused = RegexSet::fuse(&regexes).unwrap();
+
+        for text in [&b"foobarbaz"[..], b"quux", b"foo", b"justbaz"] {
+            assert_eq!(
+                fused.is_match(text),
+                a.is_match(text) || b.is_match(text) || c.is_match(text)
+            );
+
+            let expected: SmallVec<[usize; 4]> = regexes
+                .iter()
+                .enumerate()
+                .filter(|(_, r)| r.is_match(text))
+                .map(|(i, _)| i)
+                .collect();
+            assert_eq!(fused.matches(text), expected);
+        }
+    }
+
+    #[test]
+    fn regex_set_matches_returns_all_matching_indices() {
+        let set = RegexSet::new(["^foo", "bar$", "baz"]).unwrap();
+        let matches = set.matches(b"foobarbaz");
+        assert_eq!(&matches[..], &[0, 2]);
+
+        let matches = set.matches(b"quux");
+        assert!(matches.is_empty());
+    }
+
+    #[test]
+    fn cache_evicts_least_recently_used_past_capacity() {
+        let _guard = CACHE_CAPACITY_TEST_LOCK.lock().unwrap();
+
+        Regex::set_cache_capacity(NonZeroUsize::new(1).unwrap());
+
+        let first = Regex::from_str("a+").unwrap();
+        let _second = Regex::from_str("b+").unwrap();
+        let first_again = Regex::from_str("a+").unwrap();
+
+        // Capacity 1 evicted "a+" the moment "b+" was inserted, so
+        // recompiling it must produce a fresh `Arc`, not the evicted one.
+        assert!(!Arc::ptr_eq(&first.0, &first_again.0));
+
+        Regex::set_cache_capacity(NonZeroUsize::new(DEFAULT_REGEX_CACHE_CAPACITY).unwrap());
+    }
+
+    #[test]
+    fn identical_pattern_with_different_options_does_not_collide_in_cache() {
+        let plain = Regex::builder("foo").build().unwrap();
+        let case_insensitive = Regex::builder("foo").case_insensitive(true).build().unwrap();
+
+        assert!(!Arc::ptr_eq(&plain.0, &case_insensitive.0));
+        assert!(case_insensitive.is_match(b"FOO"));
+        assert!(!plain.is_match(b"FOO"));
+    }
+
+    #[test]
+    fn literal_and_compiled_paths_agree_on_matching() {
+        let literal = Regex::from_str("hello").unwrap();
+        assert!(matches!(literal.0.repr, Repr::Literal(_)));
+        assert!(literal.is_match(b"say hello world"));
+        assert!(!literal.is_match(b"say hell0 world"));
+
+        let compiled = Regex::from_str("hel+o").unwrap();
+        assert!(matches!(compiled.0.repr, Repr::Compiled(_)));
+        assert!(compiled.is_match(b"say hellllo world"));
+        assert!(!compiled.is_match(b"say heo world"));
+    }
+
+    #[test]
+    fn captures_positional_groups_on_compiled_path() {
+        let regex = Regex::from_str(r"(\d+)-(\d+)").unwrap();
+        let captures = regex.captures(b"order 12-34 placed").unwrap();
+
+        assert_eq!(captures.get(0).unwrap().as_bytes(), b"12-34");
+        assert_eq!(captures.get(1).unwrap().as_bytes(), b"12");
+        assert_eq!(captures.get(2).unwrap().as_bytes(), b"34");
+        assert!(captures.get(3).is_none());
+    }
+
+    #[test]
+    fn captures_named_groups_on_compiled_path() {
+        let regex = Regex::from_str(r"(?P<year>\d{4})-(?P<month>\d{2})").unwrap();
+        let captures = regex.captures(b"logged at 2026-07-30").unwrap();
+
+        assert_eq!(captures.name("year").unwrap().as_bytes(), b"2026");
+        assert_eq!(captures.name("month").unwrap().as_bytes(), b"07");
+        assert!(captures.name("day").is_none());
+    }
+
+    #[test]
+    fn captures_is_some_iff_is_match_on_literal_and_compiled_paths() {
+        let literal = Regex::from_str("hello").unwrap();
+        assert!(matches!(literal.0.repr, Repr::Literal(_)));
+        assert_eq!(
+            literal.captures(b"say hello world").is_some(),
+            literal.is_match(b"say hello world")
+        );
+        assert_eq!(
+            literal.captures(b"say goodbye").is_some(),
+            literal.is_match(b"say goodbye")
+        );
+        // The whole-match group must still be populated on the literal path.
+        let range = literal.captures(b"say hello world").unwrap().get(0).unwrap().range();
+        assert_eq!(range, 4..9);
+
+        let compiled = Regex::from_str(r"hel+o").unwrap();
+        assert!(matches!(compiled.0.repr, Repr::Compiled(_)));
+        assert_eq!(
+            compiled.captures(b"say hellllo world").is_some(),
+            compiled.is_match(b"say hellllo world")
+        );
+        assert_eq!(
+            compiled.captures(b"say heo world").is_some(),
+            compiled.is_match(b"say heo world")
+        );
     }
 }
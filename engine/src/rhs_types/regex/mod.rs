@@ -0,0 +1,3 @@
+mod imp_pool;
+
+pub use self::imp_pool::{Captures, Error, Match, Regex, RegexBuilder, RegexSet};